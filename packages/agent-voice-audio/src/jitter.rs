@@ -0,0 +1,179 @@
+//! Timestamped playback for streamed audio that arrives over the network
+//! with irregular timing (e.g. TTS chunks from a remote agent). Chunks are
+//! held until their presentation time is due, and the target buffering
+//! delay adapts to observed arrival jitter and how often the buffer runs
+//! dry, trading latency for robustness as conditions change.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+const MIN_DELAY_MS: f64 = 40.0;
+const MAX_DELAY_MS: f64 = 1_000.0;
+const DEFAULT_DELAY_MS: f64 = 120.0;
+const JITTER_MARGIN: f64 = 3.0;
+const EMA_ALPHA: f64 = 0.1;
+const GROW_STEP_MS: f64 = 15.0;
+const SHRINK_STEP_MS: f64 = 1.0;
+/// Minimum span of empty-queue playout clock that must elapse before a dry
+/// spell grows the target delay again. Without this, `advance` being called
+/// once per sample tick meant a single few-millisecond gap between chunks
+/// (a normal occurrence, not an underrun) could ratchet the delay all the
+/// way to `MAX_DELAY_MS` before the next chunk even arrived.
+const DRY_GROWTH_INTERVAL_MS: f64 = 50.0;
+
+/// A queue of items tagged with a presentation clock. Chunks can arrive out
+/// of order over the network, so `push` keeps the queue sorted by clock.
+pub struct ClockedQueue<T> {
+    items: VecDeque<(u64, T)>,
+}
+
+impl<T> ClockedQueue<T> {
+    pub fn new() -> Self {
+        Self {
+            items: VecDeque::new(),
+        }
+    }
+
+    pub fn push(&mut self, clock_ms: u64, item: T) {
+        let pos = self.items.partition_point(|(clock, _)| *clock <= clock_ms);
+        self.items.insert(pos, (clock_ms, item));
+    }
+
+    pub fn peek_clock(&self) -> Option<u64> {
+        self.items.front().map(|(clock, _)| *clock)
+    }
+
+    pub fn pop_next(&mut self) -> Option<(u64, T)> {
+        self.items.pop_front()
+    }
+
+    /// Push an item back onto the front, e.g. when it turns out not to be
+    /// due yet after all.
+    pub fn unpop(&mut self, clock_ms: u64, item: T) {
+        self.items.push_front((clock_ms, item));
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+}
+
+impl<T> Default for ClockedQueue<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Adaptive jitter buffer sitting in front of a `ClockedQueue` of PCM
+/// chunks. The playout clock advances once per call to `advance`, which the
+/// engine worker drives at a pace kept close to real playback; `advance`
+/// releases whatever has become due.
+pub struct JitterBuffer {
+    queue: ClockedQueue<Vec<i16>>,
+    playout_clock_ms: f64,
+    target_delay_ms: f64,
+    jitter_ema_ms: f64,
+    last_arrival: Option<(Instant, u64)>,
+    late_frames: u32,
+    /// How long the queue has been continuously empty, in playout clock ms.
+    /// Reset whenever a chunk is due or still queued; see `DRY_GROWTH_INTERVAL_MS`.
+    dry_span_ms: f64,
+}
+
+impl JitterBuffer {
+    pub fn new() -> Self {
+        Self {
+            queue: ClockedQueue::new(),
+            playout_clock_ms: 0.0,
+            target_delay_ms: DEFAULT_DELAY_MS,
+            jitter_ema_ms: 0.0,
+            last_arrival: None,
+            late_frames: 0,
+            dry_span_ms: 0.0,
+        }
+    }
+
+    /// Queue `pcm` for playout at `presentation_time_ms`, tracking arrival
+    /// jitter so the target buffering delay can adapt.
+    pub fn push(&mut self, presentation_time_ms: u64, pcm: Vec<i16>) {
+        let now = Instant::now();
+        if let Some((last_instant, last_pts)) = self.last_arrival {
+            let wall_delta_ms = now.duration_since(last_instant).as_secs_f64() * 1000.0;
+            let pts_delta_ms = presentation_time_ms as f64 - last_pts as f64;
+            let jitter = (wall_delta_ms - pts_delta_ms).abs();
+            self.jitter_ema_ms += EMA_ALPHA * (jitter - self.jitter_ema_ms);
+        }
+        self.last_arrival = Some((now, presentation_time_ms));
+
+        if (presentation_time_ms as f64) < self.playout_clock_ms {
+            self.late_frames = self.late_frames.saturating_add(1);
+        }
+
+        self.queue.push(presentation_time_ms, pcm);
+        self.adapt_target_delay();
+    }
+
+    fn adapt_target_delay(&mut self) {
+        let desired = (self.jitter_ema_ms * JITTER_MARGIN).clamp(MIN_DELAY_MS, MAX_DELAY_MS);
+        if desired > self.target_delay_ms {
+            self.target_delay_ms = (self.target_delay_ms + GROW_STEP_MS).min(desired);
+        } else {
+            self.target_delay_ms = (self.target_delay_ms - SHRINK_STEP_MS).max(desired);
+        }
+    }
+
+    /// Advance the playout clock by one target-rate sample period and
+    /// return the concatenated PCM of any chunks now due for presentation.
+    /// The caller is expected to call this at a pace that tracks real
+    /// playback (the engine worker paces its render loop to stay within a
+    /// small lookahead of what the output callback is consuming), so this
+    /// clock is a close proxy for samples actually emitted, not just ones
+    /// rendered far ahead of time. Running dry for a sustained span with
+    /// nothing queued at all grows the target delay, same as a burst of
+    /// arrival jitter would; a dry span shorter than `DRY_GROWTH_INTERVAL_MS`
+    /// is treated as the normal gap between chunks, not an underrun.
+    pub fn advance(&mut self, sample_period_ms: f64) -> Vec<i16> {
+        self.playout_clock_ms += sample_period_ms;
+        let due_before = self.playout_clock_ms - self.target_delay_ms;
+
+        let mut out = Vec::new();
+        while let Some(clock_ms) = self.queue.peek_clock() {
+            if clock_ms as f64 > due_before {
+                break;
+            }
+            if let Some((_, pcm)) = self.queue.pop_next() {
+                out.extend(pcm);
+            }
+        }
+
+        if out.is_empty() && self.queue.is_empty() {
+            self.dry_span_ms += sample_period_ms;
+            if self.dry_span_ms >= DRY_GROWTH_INTERVAL_MS {
+                self.target_delay_ms = (self.target_delay_ms + GROW_STEP_MS).min(MAX_DELAY_MS);
+                self.dry_span_ms = 0.0;
+            }
+        } else {
+            self.dry_span_ms = 0.0;
+        }
+
+        out
+    }
+
+    pub fn buffered_delay_ms(&self) -> f64 {
+        self.target_delay_ms
+    }
+
+    pub fn late_frames(&self) -> u32 {
+        self.late_frames
+    }
+}
+
+impl Default for JitterBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
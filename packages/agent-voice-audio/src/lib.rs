@@ -1,17 +1,59 @@
-use std::collections::VecDeque;
+mod jitter;
+mod resampler;
+mod wav;
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
 
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{FromSample, Sample, SampleFormat, SizedSample, Stream, StreamConfig, SupportedStreamConfig};
 use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{
+    ErrorStrategy, ThreadsafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+};
+use napi::JsFunction;
 use napi_derive::napi;
+use ringbuf::{Consumer as _, HeapConsumer, HeapProducer, HeapRb, Producer as _};
 use sonora::config::EchoCanceller as SonoraEchoCanceller;
 use sonora::{AudioProcessing, Config as SonoraConfig, StreamConfig as SonoraStreamConfig};
 
+use jitter::JitterBuffer;
+use resampler::{PolyphaseFilterBank, RESAMPLER_TAPS};
+use wav::WavWriter;
+
 const DEFAULT_SAMPLE_RATE: u32 = 24_000;
 const DEFAULT_STREAM_DELAY_MS: i32 = 30;
 const DEFAULT_MAX_CAPTURE_FRAMES: usize = 400;
 
+/// How long the lock-free rings between the cpal callbacks and the worker
+/// thread can hold, expressed in seconds of audio at their respective rates.
+/// Generous enough that neither side stalls the other under normal jitter;
+/// the worker does not fill this to capacity (see `OUTPUT_LOOKAHEAD_MS`), it
+/// only gives the RT callbacks headroom to drain bursts of worker output.
+const DEVICE_RING_SECONDS: usize = 1;
+const WORKER_IDLE_SLEEP: Duration = Duration::from_millis(1);
+
+/// How far ahead of the output callback the worker is allowed to render.
+/// Keeping this small (tens of ms, not the full `DEVICE_RING_SECONDS` ring)
+/// bounds end-to-end playback latency and keeps the worker's render cadence
+/// close to what the output callback is actually consuming in real time,
+/// which the jitter buffer's playout clock relies on.
+const OUTPUT_LOOKAHEAD_MS: u64 = 40;
+
+/// Source name `play()` writes to. The mixer starts out empty; this source
+/// is created by the first `play()` (or `add_source("default")`).
+const DEFAULT_SOURCE_NAME: &str = "default";
+
+/// Reserved mixer source the jitter buffer releases due `playAt` chunks
+/// into. `add_source` rejects this name so a caller can never get a handle
+/// onto it and silently break `playAt` playback via `clear`/`set_gain`.
+const JITTER_SOURCE_NAME: &str = "__jitter";
+
 #[napi(object)]
 pub struct AudioEngineOptions {
     pub sample_rate: Option<u32>,
@@ -19,6 +61,29 @@ pub struct AudioEngineOptions {
     pub enable_aec: Option<bool>,
     pub stream_delay_ms: Option<i32>,
     pub max_capture_frames: Option<u32>,
+    pub input_device: Option<String>,
+    pub output_device: Option<String>,
+}
+
+#[napi(object)]
+pub struct AudioDeviceConfigRange {
+    pub channels: u32,
+    pub min_sample_rate: u32,
+    pub max_sample_rate: u32,
+    pub sample_format: String,
+}
+
+#[napi(object)]
+pub struct AudioDeviceInfo {
+    pub name: String,
+    pub is_default: bool,
+    pub configs: Vec<AudioDeviceConfigRange>,
+}
+
+#[napi(object)]
+pub struct AudioDeviceList {
+    pub input: Vec<AudioDeviceInfo>,
+    pub output: Vec<AudioDeviceInfo>,
 }
 
 #[napi(object)]
@@ -29,95 +94,344 @@ pub struct AudioEngineStats {
     pub pending_playback_samples: u32,
     pub dropped_raw_frames: u32,
     pub dropped_processed_frames: u32,
+    pub sources: Vec<SourceStats>,
+    pub jitter_buffered_delay_ms: f64,
+    pub jitter_late_frames: u32,
 }
 
+#[napi(object)]
+pub struct SourceStats {
+    pub name: String,
+    pub pending_samples: u32,
+}
+
+/// Which capture stream(s) a push-based consumer wants delivered.
+#[napi(string_enum = "lowercase")]
+pub enum CaptureTrack {
+    Raw,
+    Processed,
+    Both,
+}
+
+/// A capture frame pushed to JS as soon as the worker produces it, instead
+/// of waiting for a poll of `read_raw_capture`/`read_processed_capture`.
+type CaptureCallback = ThreadsafeFunction<Buffer, ErrorStrategy::Fatal>;
+
+#[napi(object)]
+pub struct RecordingOptions {
+    pub track: Option<CaptureTrack>,
+}
+
+/// Counters touched directly from the real-time cpal callbacks. Atomics are
+/// the only thing a callback is allowed to touch besides its ring buffer.
+#[derive(Default)]
+struct Atomics {
+    playback_underruns: AtomicU32,
+    dropped_raw_frames: AtomicU32,
+    stream_delay_ms: AtomicI32,
+}
+
+/// Frame-level stats the worker thread updates as it drains the capture ring.
 #[derive(Default)]
 struct Stats {
     capture_frames: u32,
     processed_frames: u32,
-    playback_underruns: u32,
+    /// Raw frames evicted from the outbox because `read_raw_capture` hasn't
+    /// kept up and `max_capture_frames` was hit. Kept separate from
+    /// `Atomics::dropped_raw_frames`, which counts device samples dropped
+    /// entering the capture ring from the RT callback; the two are summed
+    /// in `get_stats` so the reported count covers both loss points.
     dropped_raw_frames: u32,
     dropped_processed_frames: u32,
 }
 
-struct EngineInner {
+struct PlaybackSourceState {
+    queue: VecDeque<i16>,
+    gain: f32,
+}
+
+impl Default for PlaybackSourceState {
+    fn default() -> Self {
+        Self {
+            queue: VecDeque::new(),
+            gain: 1.0,
+        }
+    }
+}
+
+/// Named playback sources the worker mixes down to a single render signal
+/// every output sample, so overlapping sounds (TTS, a chime, a hold tone)
+/// don't have to be pre-mixed in JS.
+#[derive(Default)]
+struct Mixer {
+    sources: HashMap<String, PlaybackSourceState>,
+}
+
+impl Mixer {
+    fn queue_bytes(&mut self, name: &str, pcm16: &[u8]) {
+        let source = self.sources.entry(name.to_string()).or_default();
+        for chunk in pcm16.chunks_exact(2) {
+            source.queue.push_back(i16::from_le_bytes([chunk[0], chunk[1]]));
+        }
+    }
+
+    /// Append already-decoded samples to a source's queue, creating the
+    /// source if it doesn't exist yet. Used by the worker to release
+    /// jitter-buffered chunks once they come due.
+    fn push_samples(&mut self, name: &str, samples: &[i16]) {
+        let source = self.sources.entry(name.to_string()).or_default();
+        source.queue.extend(samples);
+    }
+
+    fn set_gain(&mut self, name: &str, gain: f32) {
+        self.sources.entry(name.to_string()).or_default().gain = gain;
+    }
+
+    fn clear(&mut self, name: &str) {
+        if let Some(source) = self.sources.get_mut(name) {
+            source.queue.clear();
+        }
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.sources.remove(name);
+    }
+
+    fn stats(&self) -> Vec<SourceStats> {
+        self.sources
+            .iter()
+            .map(|(name, source)| SourceStats {
+                name: name.clone(),
+                pending_samples: source.queue.len() as u32,
+            })
+            .collect()
+    }
+
+    /// Pop one sample from every source and sum them, clamped to i16 range.
+    /// The render path feeding the AEC sees this mixed signal, so echo
+    /// cancellation still works regardless of how many sources are active.
+    fn next_mixed_sample(&mut self) -> i16 {
+        let mut acc = 0f32;
+        for source in self.sources.values_mut() {
+            if let Some(sample) = source.queue.pop_front() {
+                acc += sample as f32 * source.gain;
+            }
+        }
+        acc.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+/// Hands WAV frames off to a dedicated thread that owns the `WavWriter`, so
+/// a slow disk only ever delays that thread and never the DSP worker's
+/// capture-drain/output-refill loop. `send` is a non-blocking channel push;
+/// the actual `write_all`+flush happens off of the worker entirely.
+struct Recorder {
+    tx: mpsc::Sender<Vec<u8>>,
+    handle: Option<thread::JoinHandle<io::Result<()>>>,
+}
+
+impl Recorder {
+    fn spawn(mut writer: WavWriter) -> Self {
+        let (tx, rx) = mpsc::channel::<Vec<u8>>();
+        let handle = thread::spawn(move || {
+            while let Ok(bytes) = rx.recv() {
+                writer.write_pcm16_le(&bytes)?;
+            }
+            writer.finalize()
+        });
+        Self {
+            tx,
+            handle: Some(handle),
+        }
+    }
+
+    /// Queue `bytes` for the recorder thread to write. Never blocks on disk.
+    fn send(&self, bytes: Vec<u8>) {
+        let _ = self.tx.send(bytes);
+    }
+
+    /// Drop the sender so the recorder thread's `recv` loop ends, then join
+    /// it to surface the finalized file's result.
+    fn stop(self) -> io::Result<()> {
+        let Recorder { tx, mut handle } = self;
+        drop(tx);
+        match handle.take() {
+            Some(h) => h
+                .join()
+                .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::Other, "recorder thread panicked"))),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The only state shared between the worker thread (writer) and JS-facing
+/// methods (reader). Never touched by the RT audio callbacks.
+struct Outbox {
+    raw_frames: VecDeque<Vec<u8>>,
+    processed_frames: VecDeque<Vec<u8>>,
+    stats: Stats,
+    max_capture_frames: usize,
+    /// Push-based consumers registered via `on_capture`. Polling via
+    /// `read_raw_capture`/`read_processed_capture` keeps working regardless
+    /// of whether these are set.
+    capture_raw_callback: Option<CaptureCallback>,
+    capture_processed_callback: Option<CaptureCallback>,
+    /// Active WAV recordings, if any, tapping the same frames that feed
+    /// `raw_frames`/`processed_frames` and `on_capture`. Each runs its own
+    /// writer thread so recording I/O never blocks the DSP worker.
+    raw_recorder: Option<Recorder>,
+    processed_recorder: Option<Recorder>,
+}
+
+impl Outbox {
+    fn new(max_capture_frames: usize) -> Self {
+        Self {
+            raw_frames: VecDeque::new(),
+            processed_frames: VecDeque::new(),
+            stats: Stats::default(),
+            max_capture_frames,
+            capture_raw_callback: None,
+            capture_processed_callback: None,
+            raw_recorder: None,
+            processed_recorder: None,
+        }
+    }
+
+    fn pop_raw_frames(&mut self, limit: usize) -> Vec<Buffer> {
+        pop_frames(&mut self.raw_frames, limit)
+    }
+
+    fn pop_processed_frames(&mut self, limit: usize) -> Vec<Buffer> {
+        pop_frames(&mut self.processed_frames, limit)
+    }
+}
+
+/// The DSP pipeline (resampling + APM). Owned exclusively by the worker
+/// thread so it never needs a lock.
+struct DspState {
     target_sample_rate: u32,
     frame_size: usize,
     enable_aec: bool,
-    max_capture_frames: usize,
     apm: AudioProcessing,
     stream: SonoraStreamConfig,
-    playback_queue: VecDeque<i16>,
     render_accum: Vec<i16>,
     capture_accum: Vec<i16>,
-    raw_frames: VecDeque<Vec<u8>>,
-    processed_frames: VecDeque<Vec<u8>>,
     playback_device_rate: u32,
     capture_device_rate: u32,
     playback_step_accum: u64,
     capture_step_accum: u64,
-    last_playback_sample: i16,
-    stats: Stats,
+    render_bank: PolyphaseFilterBank,
+    capture_bank: PolyphaseFilterBank,
+    render_history: [i16; RESAMPLER_TAPS],
+    capture_history: [i16; RESAMPLER_TAPS],
+    applied_stream_delay_ms: i32,
 }
 
-unsafe impl Send for EngineInner {}
+unsafe impl Send for DspState {}
 
-impl EngineInner {
-    fn set_stream_delay(&mut self, delay_ms: i32) {
-        let _ = self.apm.set_stream_delay_ms(delay_ms);
-    }
-
-    fn set_device_rates(&mut self, playback_rate: u32, capture_rate: u32) {
-        self.playback_device_rate = playback_rate.max(1);
-        self.capture_device_rate = capture_rate.max(1);
-        self.playback_step_accum = self.playback_device_rate as u64;
-        self.capture_step_accum = self.capture_device_rate as u64;
-    }
-
-    fn queue_playback_bytes(&mut self, pcm16: &[u8]) {
-        for chunk in pcm16.chunks_exact(2) {
-            self.playback_queue
-                .push_back(i16::from_le_bytes([chunk[0], chunk[1]]));
+impl DspState {
+    fn new(
+        target_sample_rate: u32,
+        frame_size: usize,
+        enable_aec: bool,
+        apm: AudioProcessing,
+        stream: SonoraStreamConfig,
+        stream_delay_ms: i32,
+        playback_device_rate: u32,
+        capture_device_rate: u32,
+    ) -> Self {
+        Self {
+            target_sample_rate,
+            frame_size,
+            enable_aec,
+            apm,
+            stream,
+            render_accum: Vec::with_capacity(frame_size * 2),
+            capture_accum: Vec::with_capacity(frame_size * 2),
+            playback_device_rate,
+            capture_device_rate,
+            playback_step_accum: playback_device_rate as u64,
+            capture_step_accum: capture_device_rate as u64,
+            render_bank: PolyphaseFilterBank::new(target_sample_rate, playback_device_rate),
+            capture_bank: PolyphaseFilterBank::new(capture_device_rate, target_sample_rate),
+            render_history: [0i16; RESAMPLER_TAPS],
+            capture_history: [0i16; RESAMPLER_TAPS],
+            applied_stream_delay_ms: stream_delay_ms,
         }
     }
 
-    fn consume_playback_source_sample(&mut self) -> i16 {
-        let sample = if let Some(next) = self.playback_queue.pop_front() {
-            next
-        } else {
-            self.stats.playback_underruns = self.stats.playback_underruns.saturating_add(1);
-            0
-        };
-        self.last_playback_sample = sample;
-        self.render_accum.push(sample);
-        self.process_render_frames();
-        sample
+    fn sync_stream_delay(&mut self, atomics: &Atomics) {
+        let wanted = atomics.stream_delay_ms.load(Ordering::Relaxed);
+        if wanted != self.applied_stream_delay_ms {
+            let _ = self.apm.set_stream_delay_ms(wanted);
+            self.applied_stream_delay_ms = wanted;
+        }
     }
 
-    fn next_output_sample(&mut self) -> i16 {
+    /// Pull one resampled, device-rate output sample, mixing down whatever
+    /// the active playback sources have queued as needed. Before mixing,
+    /// advances the jitter buffer's playout clock by one target-rate tick
+    /// and releases any `playAt` chunks that have become due into its
+    /// reserved mixer source.
+    fn next_output_sample(&mut self, mixer: &Mutex<Mixer>, jitter: &Mutex<JitterBuffer>) -> i16 {
         self.playback_step_accum = self
             .playback_step_accum
             .saturating_add(self.target_sample_rate as u64);
 
         while self.playback_step_accum >= self.playback_device_rate as u64 {
             self.playback_step_accum -= self.playback_device_rate as u64;
-            let _ = self.consume_playback_source_sample();
+
+            let sample_period_ms = 1000.0 / self.target_sample_rate as f64;
+            let due = match jitter.lock() {
+                Ok(mut guard) => guard.advance(sample_period_ms),
+                Err(poisoned) => poisoned.into_inner().advance(sample_period_ms),
+            };
+
+            let sample = match mixer.lock() {
+                Ok(mut guard) => {
+                    if !due.is_empty() {
+                        guard.push_samples(JITTER_SOURCE_NAME, &due);
+                    }
+                    guard.next_mixed_sample()
+                }
+                Err(poisoned) => {
+                    let mut guard = poisoned.into_inner();
+                    if !due.is_empty() {
+                        guard.push_samples(JITTER_SOURCE_NAME, &due);
+                    }
+                    guard.next_mixed_sample()
+                }
+            };
+            self.render_history.rotate_left(1);
+            *self.render_history.last_mut().unwrap() = sample;
+            self.render_accum.push(sample);
+            self.process_render_frames();
         }
 
-        self.last_playback_sample
+        let frac = self.playback_step_accum as f64 / self.playback_device_rate as f64;
+        let phase = PolyphaseFilterBank::phase_for_frac(frac);
+        self.render_bank.convolve(&self.render_history, phase)
     }
 
-    fn on_captured_device_sample(&mut self, sample: i16) {
+    /// Feed one raw, device-rate capture sample, resampling it to the
+    /// target rate and handing finished frames off to `outbox`.
+    fn on_captured_device_sample(&mut self, sample: i16, outbox: &Mutex<Outbox>) {
+        self.capture_history.rotate_left(1);
+        *self.capture_history.last_mut().unwrap() = sample;
+
         self.capture_step_accum = self
             .capture_step_accum
             .saturating_add(self.target_sample_rate as u64);
 
         while self.capture_step_accum >= self.capture_device_rate as u64 {
             self.capture_step_accum -= self.capture_device_rate as u64;
-            self.capture_accum.push(sample);
+            let frac = self.capture_step_accum as f64 / self.capture_device_rate as f64;
+            let phase = PolyphaseFilterBank::phase_for_frac(frac);
+            let resampled = self.capture_bank.convolve(&self.capture_history, phase);
+            self.capture_accum.push(resampled);
         }
 
-        self.process_capture_frames();
+        self.process_capture_frames(outbox);
     }
 
     fn process_render_frames(&mut self) {
@@ -133,21 +447,13 @@ impl EngineInner {
         }
     }
 
-    fn process_capture_frames(&mut self) {
+    fn process_capture_frames(&mut self, outbox: &Mutex<Outbox>) {
         while self.capture_accum.len() >= self.frame_size {
             let mut frame = vec![0i16; self.frame_size];
             frame.copy_from_slice(&self.capture_accum[..self.frame_size]);
             self.capture_accum.drain(..self.frame_size);
-            self.stats.capture_frames = self.stats.capture_frames.saturating_add(1);
-
-            let raw = pcm16_to_bytes(&frame);
-            push_frame_with_cap(
-                &mut self.raw_frames,
-                raw,
-                self.max_capture_frames,
-                &mut self.stats.dropped_raw_frames,
-            );
 
+            let raw_bytes = pcm16_to_bytes(&frame);
             let processed = if self.enable_aec {
                 let mut out = vec![0i16; self.frame_size];
                 let _ = self.apm.process_capture_i16_with_config(
@@ -160,27 +466,60 @@ impl EngineInner {
             } else {
                 frame
             };
-
-            self.stats.processed_frames = self.stats.processed_frames.saturating_add(1);
             let processed_bytes = pcm16_to_bytes(&processed);
-            push_frame_with_cap(
-                &mut self.processed_frames,
-                processed_bytes,
-                self.max_capture_frames,
-                &mut self.stats.dropped_processed_frames,
-            );
-        }
-    }
 
-    fn pop_processed_frames(&mut self, limit: usize) -> Vec<Buffer> {
-        pop_frames(&mut self.processed_frames, limit)
-    }
+            let mut guard = match outbox.lock() {
+                Ok(guard) => guard,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            guard.stats.capture_frames = guard.stats.capture_frames.saturating_add(1);
+            guard.stats.processed_frames = guard.stats.processed_frames.saturating_add(1);
 
-    fn pop_raw_frames(&mut self, limit: usize) -> Vec<Buffer> {
-        pop_frames(&mut self.raw_frames, limit)
+            if let Some(cb) = &guard.capture_raw_callback {
+                cb.call(Buffer::from(raw_bytes.clone()), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+            if let Some(recorder) = &guard.raw_recorder {
+                recorder.send(raw_bytes.clone());
+            }
+            let cap = guard.max_capture_frames;
+            if guard.raw_frames.len() >= cap {
+                guard.raw_frames.pop_front();
+                guard.stats.dropped_raw_frames = guard.stats.dropped_raw_frames.saturating_add(1);
+            }
+            guard.raw_frames.push_back(raw_bytes);
+
+            if let Some(cb) = &guard.capture_processed_callback {
+                cb.call(Buffer::from(processed_bytes.clone()), ThreadsafeFunctionCallMode::NonBlocking);
+            }
+            if let Some(recorder) = &guard.processed_recorder {
+                recorder.send(processed_bytes.clone());
+            }
+            if guard.processed_frames.len() >= cap {
+                guard.processed_frames.pop_front();
+                guard.stats.dropped_processed_frames =
+                    guard.stats.dropped_processed_frames.saturating_add(1);
+            }
+            guard.processed_frames.push_back(processed_bytes);
+        }
     }
 }
 
+/// Build a threadsafe function wrapping `callback` for `on_capture`. When
+/// `tag` is set (used for `CaptureTrack::Both`, where the same JS function
+/// backs both capture slots) it's passed as a leading string argument ahead
+/// of the frame `Buffer`, since a single function invoked twice with
+/// nothing but a same-sized `Buffer` has no way to tell which call was raw
+/// and which was echo-cancelled.
+fn make_capture_tsfn(callback: &JsFunction, tag: Option<&'static str>) -> Result<CaptureCallback> {
+    callback.create_threadsafe_function(0, move |ctx: ThreadsafeCallContext<Buffer>| match tag {
+        Some(tag) => Ok(vec![
+            ctx.env.create_string(tag)?.into_unknown(),
+            ctx.value.into_unknown(ctx.env)?,
+        ]),
+        None => Ok(vec![ctx.value.into_unknown(ctx.env)?]),
+    })
+}
+
 fn pop_frames(queue: &mut VecDeque<Vec<u8>>, limit: usize) -> Vec<Buffer> {
     let take = limit.min(queue.len());
     let mut out = Vec::with_capacity(take);
@@ -192,19 +531,6 @@ fn pop_frames(queue: &mut VecDeque<Vec<u8>>, limit: usize) -> Vec<Buffer> {
     out
 }
 
-fn push_frame_with_cap(
-    queue: &mut VecDeque<Vec<u8>>,
-    frame: Vec<u8>,
-    cap: usize,
-    dropped_counter: &mut u32,
-) {
-    if queue.len() >= cap {
-        queue.pop_front();
-        *dropped_counter = dropped_counter.saturating_add(1);
-    }
-    queue.push_back(frame);
-}
-
 fn pcm16_to_bytes(samples: &[i16]) -> Vec<u8> {
     let mut out = vec![0u8; samples.len() * 2];
     for (idx, sample) in samples.iter().enumerate() {
@@ -215,6 +541,61 @@ fn pcm16_to_bytes(samples: &[i16]) -> Vec<u8> {
     out
 }
 
+/// Derive a per-track filename for `CaptureTrack::Both` recordings by
+/// inserting `suffix` before the extension (or appending it if there is
+/// none), e.g. `call.wav` + `"raw"` -> `call.raw.wav`.
+fn recording_path(path: &str, suffix: &str) -> String {
+    let name_start = path.rfind(|c: char| c == '/' || c == '\\').map_or(0, |sep| sep + 1);
+    match path[name_start..].rfind('.').map(|dot| name_start + dot) {
+        Some(dot) => format!("{}.{}{}", &path[..dot], suffix, &path[dot..]),
+        None => format!("{path}.{suffix}"),
+    }
+}
+
+fn collect_device_info<I>(
+    devices: impl Iterator<Item = cpal::Device>,
+    default_name: &Option<String>,
+    supported_configs: impl Fn(&cpal::Device) -> std::result::Result<I, cpal::SupportedStreamConfigsError>,
+) -> Vec<AudioDeviceInfo>
+where
+    I: Iterator<Item = cpal::SupportedStreamConfigRange>,
+{
+    devices
+        .filter_map(|device| {
+            let name = device.name().ok()?;
+            let is_default = default_name.as_deref() == Some(name.as_str());
+            let configs = supported_configs(&device)
+                .map(|ranges| {
+                    ranges
+                        .map(|range| AudioDeviceConfigRange {
+                            channels: range.channels() as u32,
+                            min_sample_rate: range.min_sample_rate().0,
+                            max_sample_rate: range.max_sample_rate().0,
+                            sample_format: format!("{:?}", range.sample_format()),
+                        })
+                        .collect()
+                })
+                .unwrap_or_default();
+            Some(AudioDeviceInfo {
+                name,
+                is_default,
+                configs,
+            })
+        })
+        .collect()
+}
+
+fn resolve_device(
+    name: Option<&str>,
+    mut devices: impl Iterator<Item = cpal::Device>,
+    default: impl FnOnce() -> Option<cpal::Device>,
+) -> Option<cpal::Device> {
+    match name {
+        Some(name) => devices.find(|device| device.name().map(|n| n == name).unwrap_or(false)),
+        None => default(),
+    }
+}
+
 fn create_apm(sample_rate: u32, stream_delay_ms: i32) -> Result<(AudioProcessing, SonoraStreamConfig)> {
     let config = SonoraConfig {
         echo_canceller: Some(SonoraEchoCanceller::default()),
@@ -231,15 +612,90 @@ fn create_apm(sample_rate: u32, stream_delay_ms: i32) -> Result<(AudioProcessing
     Ok((apm, stream))
 }
 
+/// Handle to the background worker that owns the DSP pipeline. Stopping the
+/// engine signals the thread to exit and joins it so no worker ever outlives
+/// its streams.
+struct Worker {
+    stop: Arc<AtomicBool>,
+    handle: Option<thread::JoinHandle<()>>,
+}
+
+impl Worker {
+    fn stop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
 #[napi]
 pub struct AudioEngine {
-    inner: Arc<Mutex<EngineInner>>,
+    target_sample_rate: u32,
+    frame_size: usize,
+    enable_aec: bool,
+    max_capture_frames: usize,
+    input_device_name: Option<String>,
+    output_device_name: Option<String>,
+    atomics: Arc<Atomics>,
+    outbox: Arc<Mutex<Outbox>>,
+    mixer: Arc<Mutex<Mixer>>,
+    jitter: Arc<Mutex<JitterBuffer>>,
     input_stream: Option<Stream>,
     output_stream: Option<Stream>,
+    worker: Option<Worker>,
 }
 
 unsafe impl Send for AudioEngine {}
 
+/// A handle to one named channel in the engine's `Mixer`. Independent
+/// from any other source's queue and gain, so e.g. agent TTS, a notification
+/// chime, and a hold tone can all play at once without JS mixing them first.
+#[napi]
+pub struct PlaybackSource {
+    name: String,
+    mixer: Arc<Mutex<Mixer>>,
+}
+
+#[napi]
+impl PlaybackSource {
+    #[napi]
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    #[napi]
+    pub fn play(&self, pcm16: Buffer) -> Result<()> {
+        if pcm16.len() % 2 != 0 {
+            return Err(Error::from_reason("play() expects 16-bit PCM (even byte length)"));
+        }
+        let mut mixer = self.mixer.lock().map_err(|_| Error::from_reason("mixer lock poisoned"))?;
+        mixer.queue_bytes(&self.name, pcm16.as_ref());
+        Ok(())
+    }
+
+    #[napi]
+    pub fn set_gain(&self, gain: f64) -> Result<()> {
+        let mut mixer = self.mixer.lock().map_err(|_| Error::from_reason("mixer lock poisoned"))?;
+        mixer.set_gain(&self.name, gain as f32);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn clear(&self) -> Result<()> {
+        let mut mixer = self.mixer.lock().map_err(|_| Error::from_reason("mixer lock poisoned"))?;
+        mixer.clear(&self.name);
+        Ok(())
+    }
+
+    #[napi]
+    pub fn remove(&self) -> Result<()> {
+        let mut mixer = self.mixer.lock().map_err(|_| Error::from_reason("mixer lock poisoned"))?;
+        mixer.remove(&self.name);
+        Ok(())
+    }
+}
+
 #[napi]
 impl AudioEngine {
     #[napi(constructor)]
@@ -261,32 +717,68 @@ impl AudioEngine {
             .as_ref()
             .and_then(|o| o.max_capture_frames)
             .unwrap_or(DEFAULT_MAX_CAPTURE_FRAMES as u32) as usize;
+        let input_device_name = options.as_ref().and_then(|o| o.input_device.clone());
+        let output_device_name = options.as_ref().and_then(|o| o.output_device.clone());
 
         let frame_size = (sample_rate / 100) as usize;
-        let (apm, stream) = create_apm(sample_rate, stream_delay_ms)?;
+        let atomics = Arc::new(Atomics {
+            playback_underruns: AtomicU32::new(0),
+            dropped_raw_frames: AtomicU32::new(0),
+            stream_delay_ms: AtomicI32::new(stream_delay_ms),
+        });
 
         Ok(Self {
-            inner: Arc::new(Mutex::new(EngineInner {
-                target_sample_rate: sample_rate,
-                frame_size,
-                enable_aec,
-                max_capture_frames,
-                apm,
-                stream,
-                playback_queue: VecDeque::new(),
-                render_accum: Vec::with_capacity(frame_size * 2),
-                capture_accum: Vec::with_capacity(frame_size * 2),
-                raw_frames: VecDeque::new(),
-                processed_frames: VecDeque::new(),
-                playback_device_rate: sample_rate,
-                capture_device_rate: sample_rate,
-                playback_step_accum: sample_rate as u64,
-                capture_step_accum: sample_rate as u64,
-                last_playback_sample: 0,
-                stats: Stats::default(),
-            })),
+            target_sample_rate: sample_rate,
+            frame_size,
+            enable_aec,
+            max_capture_frames,
+            input_device_name,
+            output_device_name,
+            atomics,
+            outbox: Arc::new(Mutex::new(Outbox::new(max_capture_frames))),
+            mixer: Arc::new(Mutex::new(Mixer::default())),
+            jitter: Arc::new(Mutex::new(JitterBuffer::new())),
             input_stream: None,
             output_stream: None,
+            worker: None,
+        })
+    }
+
+    /// Register a new named playback channel, or return a handle to it if
+    /// it already exists. Each source has its own queue and gain; the
+    /// worker thread mixes every active source's next sample together.
+    #[napi]
+    pub fn add_source(&self, name: String) -> Result<PlaybackSource> {
+        if name == JITTER_SOURCE_NAME {
+            return Err(Error::from_reason(format!("'{JITTER_SOURCE_NAME}' is a reserved source name")));
+        }
+        let mut mixer = self.mixer.lock().map_err(|_| Error::from_reason("mixer lock poisoned"))?;
+        mixer.sources.entry(name.clone()).or_default();
+        drop(mixer);
+        Ok(PlaybackSource {
+            name,
+            mixer: Arc::clone(&self.mixer),
+        })
+    }
+
+    /// Enumerate the input and output devices available on the default host,
+    /// along with the sample-rate/format ranges each one supports.
+    #[napi]
+    pub fn list_devices() -> Result<AudioDeviceList> {
+        let host = cpal::default_host();
+        let default_input_name = host.default_input_device().and_then(|d| d.name().ok());
+        let default_output_name = host.default_output_device().and_then(|d| d.name().ok());
+
+        let input_devices = host
+            .input_devices()
+            .map_err(|err| Error::from_reason(format!("failed to enumerate input devices: {err}")))?;
+        let output_devices = host
+            .output_devices()
+            .map_err(|err| Error::from_reason(format!("failed to enumerate output devices: {err}")))?;
+
+        Ok(AudioDeviceList {
+            input: collect_device_info(input_devices, &default_input_name, |d| d.supported_input_configs()),
+            output: collect_device_info(output_devices, &default_output_name, |d| d.supported_output_configs()),
         })
     }
 
@@ -297,12 +789,20 @@ impl AudioEngine {
         }
 
         let host = cpal::default_host();
-        let input = host
-            .default_input_device()
-            .ok_or_else(|| Error::from_reason("no default input device"))?;
-        let output = host
-            .default_output_device()
-            .ok_or_else(|| Error::from_reason("no default output device"))?;
+        let input = resolve_device(
+            self.input_device_name.as_deref(),
+            host.input_devices()
+                .map_err(|err| Error::from_reason(format!("failed to enumerate input devices: {err}")))?,
+            || host.default_input_device(),
+        )
+        .ok_or_else(|| Error::from_reason("no matching input device"))?;
+        let output = resolve_device(
+            self.output_device_name.as_deref(),
+            host.output_devices()
+                .map_err(|err| Error::from_reason(format!("failed to enumerate output devices: {err}")))?,
+            || host.default_output_device(),
+        )
+        .ok_or_else(|| Error::from_reason("no matching output device"))?;
 
         let input_cfg = input
             .default_input_config()
@@ -311,16 +811,40 @@ impl AudioEngine {
             .default_output_config()
             .map_err(|err| Error::from_reason(format!("default output config failed: {err}")))?;
 
-        {
-            let mut guard = self
-                .inner
-                .lock()
-                .map_err(|_| Error::from_reason("audio engine lock poisoned"))?;
-            guard.set_device_rates(output_cfg.sample_rate().0, input_cfg.sample_rate().0);
-        }
+        let playback_device_rate = output_cfg.sample_rate().0;
+        let capture_device_rate = input_cfg.sample_rate().0;
+
+        let (apm, stream) = create_apm(self.target_sample_rate, self.atomics.stream_delay_ms.load(Ordering::Relaxed))?;
+        let dsp = DspState::new(
+            self.target_sample_rate,
+            self.frame_size,
+            self.enable_aec,
+            apm,
+            stream,
+            self.atomics.stream_delay_ms.load(Ordering::Relaxed),
+            playback_device_rate,
+            capture_device_rate,
+        );
+
+        let capture_rb = HeapRb::<i16>::new((capture_device_rate as usize * DEVICE_RING_SECONDS).max(1));
+        let (capture_tx, capture_rx) = capture_rb.split();
+        let output_rb = HeapRb::<i16>::new((playback_device_rate as usize * DEVICE_RING_SECONDS).max(1));
+        let (output_tx, output_rx) = output_rb.split();
 
-        let input_stream = build_input_stream(&input, &input_cfg, Arc::clone(&self.inner))?;
-        let output_stream = build_output_stream(&output, &output_cfg, Arc::clone(&self.inner))?;
+        let input_stream = build_input_stream(&input, &input_cfg, capture_tx, Arc::clone(&self.atomics))?;
+        let output_stream = build_output_stream(&output, &output_cfg, output_rx, Arc::clone(&self.atomics))?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let handle = spawn_worker(
+            dsp,
+            capture_rx,
+            output_tx,
+            Arc::clone(&self.mixer),
+            Arc::clone(&self.jitter),
+            Arc::clone(&self.outbox),
+            Arc::clone(&self.atomics),
+            Arc::clone(&stop),
+        );
 
         input_stream
             .play()
@@ -331,6 +855,10 @@ impl AudioEngine {
 
         self.input_stream = Some(input_stream);
         self.output_stream = Some(output_stream);
+        self.worker = Some(Worker {
+            stop,
+            handle: Some(handle),
+        });
         Ok(())
     }
 
@@ -338,6 +866,9 @@ impl AudioEngine {
     pub fn stop(&mut self) -> Result<()> {
         self.input_stream.take();
         self.output_stream.take();
+        if let Some(mut worker) = self.worker.take() {
+            worker.stop();
+        }
         Ok(())
     }
 
@@ -346,16 +877,133 @@ impl AudioEngine {
         self.stop()
     }
 
+    /// Convenience that writes to the default playback source; equivalent
+    /// to `add_source("default").play(pcm16)`.
     #[napi]
     pub fn play(&self, pcm16: Buffer) -> Result<()> {
         if pcm16.len() % 2 != 0 {
             return Err(Error::from_reason("play() expects 16-bit PCM (even byte length)"));
         }
+        let mut mixer = self.mixer.lock().map_err(|_| Error::from_reason("mixer lock poisoned"))?;
+        mixer.queue_bytes(DEFAULT_SOURCE_NAME, pcm16.as_ref());
+        Ok(())
+    }
+
+    /// Queue `pcm16` for playout at `presentation_time_ms` on the engine's
+    /// playout clock, rather than as soon as the worker gets to it. Meant
+    /// for streamed TTS chunks arriving over the network with irregular
+    /// timing: the jitter buffer holds each chunk until it's due and grows
+    /// or shrinks its target delay based on observed arrival jitter and how
+    /// often it runs dry.
+    #[napi]
+    pub fn play_at(&self, pcm16: Buffer, presentation_time_ms: f64) -> Result<()> {
+        if pcm16.len() % 2 != 0 {
+            return Err(Error::from_reason("play_at() expects 16-bit PCM (even byte length)"));
+        }
+        let samples: Vec<i16> = pcm16
+            .as_ref()
+            .chunks_exact(2)
+            .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]]))
+            .collect();
+        let mut jitter = self.jitter.lock().map_err(|_| Error::from_reason("jitter buffer lock poisoned"))?;
+        jitter.push(presentation_time_ms.max(0.0) as u64, samples);
+        Ok(())
+    }
+
+    /// Register a push-based capture consumer: `callback` is invoked from
+    /// the engine worker every time a frame on `track` (default `processed`)
+    /// is produced, instead of requiring JS to poll
+    /// `read_raw_capture`/`read_processed_capture` on a timer. For `raw` or
+    /// `processed` alone the callback is invoked with just the frame
+    /// `Buffer`, unchanged from a single-track registration. For `both`,
+    /// `callback` backs two independent threadsafe functions, one per
+    /// stream, and each call is prefixed with a `"raw"`/`"processed"` tag
+    /// argument so the same function can tell the two streams apart. Only
+    /// the most recently registered callback per track is kept.
+    #[napi]
+    pub fn on_capture(&self, callback: JsFunction, track: Option<CaptureTrack>) -> Result<()> {
+        let track = track.unwrap_or(CaptureTrack::Processed);
+
         let mut guard = self
-            .inner
+            .outbox
             .lock()
-            .map_err(|_| Error::from_reason("audio engine lock poisoned"))?;
-        guard.queue_playback_bytes(pcm16.as_ref());
+            .map_err(|_| Error::from_reason("audio engine outbox lock poisoned"))?;
+        match track {
+            CaptureTrack::Raw => {
+                guard.capture_raw_callback = Some(make_capture_tsfn(&callback, None)?);
+            }
+            CaptureTrack::Processed => {
+                guard.capture_processed_callback = Some(make_capture_tsfn(&callback, None)?);
+            }
+            CaptureTrack::Both => {
+                guard.capture_raw_callback = Some(make_capture_tsfn(&callback, Some("raw"))?);
+                guard.capture_processed_callback = Some(make_capture_tsfn(&callback, Some("processed"))?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Start writing WAV file(s) from the same frame pipeline that feeds
+    /// `raw_frames`/`processed_frames`. Each recording runs its own writer
+    /// thread (see `Recorder`), so a slow disk only ever delays that thread,
+    /// never the DSP worker's capture-drain/output-refill loop. `track`
+    /// defaults to `processed`; `both` derives two filenames from `path`
+    /// (e.g. `call.wav` -> `call.raw.wav` and `call.processed.wav`) since a
+    /// single file can't hold both streams.
+    #[napi]
+    pub fn start_recording(&self, path: String, options: Option<RecordingOptions>) -> Result<()> {
+        let track = options.and_then(|o| o.track).unwrap_or(CaptureTrack::Processed);
+        let mut guard = self
+            .outbox
+            .lock()
+            .map_err(|_| Error::from_reason("audio engine outbox lock poisoned"))?;
+
+        match track {
+            CaptureTrack::Raw => {
+                let writer = WavWriter::create(&path, self.target_sample_rate)
+                    .map_err(|err| Error::from_reason(format!("failed to start raw recording: {err}")))?;
+                guard.raw_recorder = Some(Recorder::spawn(writer));
+            }
+            CaptureTrack::Processed => {
+                let writer = WavWriter::create(&path, self.target_sample_rate)
+                    .map_err(|err| Error::from_reason(format!("failed to start processed recording: {err}")))?;
+                guard.processed_recorder = Some(Recorder::spawn(writer));
+            }
+            CaptureTrack::Both => {
+                let raw_writer = WavWriter::create(&recording_path(&path, "raw"), self.target_sample_rate)
+                    .map_err(|err| Error::from_reason(format!("failed to start raw recording: {err}")))?;
+                guard.raw_recorder = Some(Recorder::spawn(raw_writer));
+
+                let processed_writer =
+                    WavWriter::create(&recording_path(&path, "processed"), self.target_sample_rate)
+                        .map_err(|err| Error::from_reason(format!("failed to start processed recording: {err}")))?;
+                guard.processed_recorder = Some(Recorder::spawn(processed_writer));
+            }
+        }
+        Ok(())
+    }
+
+    /// Finalize any active recordings' RIFF/data sizes and stop tapping the
+    /// capture pipeline, joining each recorder thread to surface its result.
+    #[napi]
+    pub fn stop_recording(&self) -> Result<()> {
+        let (raw_recorder, processed_recorder) = {
+            let mut guard = self
+                .outbox
+                .lock()
+                .map_err(|_| Error::from_reason("audio engine outbox lock poisoned"))?;
+            (guard.raw_recorder.take(), guard.processed_recorder.take())
+        };
+        if let Some(recorder) = raw_recorder {
+            recorder
+                .stop()
+                .map_err(|err| Error::from_reason(format!("failed to finalize raw recording: {err}")))?;
+        }
+        if let Some(recorder) = processed_recorder {
+            recorder
+                .stop()
+                .map_err(|err| Error::from_reason(format!("failed to finalize processed recording: {err}")))?;
+        }
         Ok(())
     }
 
@@ -363,9 +1011,9 @@ impl AudioEngine {
     pub fn read_processed_capture(&self, max_frames: Option<u32>) -> Result<Vec<Buffer>> {
         let limit = max_frames.unwrap_or(64) as usize;
         let mut guard = self
-            .inner
+            .outbox
             .lock()
-            .map_err(|_| Error::from_reason("audio engine lock poisoned"))?;
+            .map_err(|_| Error::from_reason("audio engine outbox lock poisoned"))?;
         Ok(guard.pop_processed_frames(limit))
     }
 
@@ -373,43 +1021,97 @@ impl AudioEngine {
     pub fn read_raw_capture(&self, max_frames: Option<u32>) -> Result<Vec<Buffer>> {
         let limit = max_frames.unwrap_or(64) as usize;
         let mut guard = self
-            .inner
+            .outbox
             .lock()
-            .map_err(|_| Error::from_reason("audio engine lock poisoned"))?;
+            .map_err(|_| Error::from_reason("audio engine outbox lock poisoned"))?;
         Ok(guard.pop_raw_frames(limit))
     }
 
     #[napi]
     pub fn set_stream_delay_ms(&self, delay_ms: i32) -> Result<()> {
-        let mut guard = self
-            .inner
-            .lock()
-            .map_err(|_| Error::from_reason("audio engine lock poisoned"))?;
-        guard.set_stream_delay(delay_ms);
+        self.atomics.stream_delay_ms.store(delay_ms, Ordering::Relaxed);
         Ok(())
     }
 
     #[napi]
     pub fn get_stats(&self) -> Result<AudioEngineStats> {
         let guard = self
-            .inner
+            .outbox
             .lock()
-            .map_err(|_| Error::from_reason("audio engine lock poisoned"))?;
+            .map_err(|_| Error::from_reason("audio engine outbox lock poisoned"))?;
+        let sources = self
+            .mixer
+            .lock()
+            .map_err(|_| Error::from_reason("mixer lock poisoned"))?
+            .stats();
+        let pending_playback_samples = sources.iter().map(|s| s.pending_samples).sum();
+        let (jitter_buffered_delay_ms, jitter_late_frames) = {
+            let jitter = self.jitter.lock().map_err(|_| Error::from_reason("jitter buffer lock poisoned"))?;
+            (jitter.buffered_delay_ms(), jitter.late_frames())
+        };
         Ok(AudioEngineStats {
             capture_frames: guard.stats.capture_frames,
             processed_frames: guard.stats.processed_frames,
-            playback_underruns: guard.stats.playback_underruns,
-            pending_playback_samples: guard.playback_queue.len() as u32,
-            dropped_raw_frames: guard.stats.dropped_raw_frames,
+            playback_underruns: self.atomics.playback_underruns.load(Ordering::Relaxed),
+            pending_playback_samples,
+            dropped_raw_frames: self
+                .atomics
+                .dropped_raw_frames
+                .load(Ordering::Relaxed)
+                .saturating_add(guard.stats.dropped_raw_frames),
             dropped_processed_frames: guard.stats.dropped_processed_frames,
+            sources,
+            jitter_buffered_delay_ms,
+            jitter_late_frames,
         })
     }
 }
 
+#[allow(clippy::too_many_arguments)]
+fn spawn_worker(
+    mut dsp: DspState,
+    mut capture_rx: HeapConsumer<i16>,
+    mut output_tx: HeapProducer<i16>,
+    mixer: Arc<Mutex<Mixer>>,
+    jitter: Arc<Mutex<JitterBuffer>>,
+    outbox: Arc<Mutex<Outbox>>,
+    atomics: Arc<Atomics>,
+    stop: Arc<AtomicBool>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let lookahead_samples =
+            ((dsp.playback_device_rate as u64 * OUTPUT_LOOKAHEAD_MS) / 1000).max(1) as usize;
+
+        while !stop.load(Ordering::Acquire) {
+            let mut did_work = false;
+
+            dsp.sync_stream_delay(&atomics);
+
+            while let Some(sample) = capture_rx.pop() {
+                did_work = true;
+                dsp.on_captured_device_sample(sample, &outbox);
+            }
+
+            while output_tx.len() < lookahead_samples {
+                did_work = true;
+                let sample = dsp.next_output_sample(&mixer, &jitter);
+                if output_tx.push(sample).is_err() {
+                    break;
+                }
+            }
+
+            if !did_work {
+                thread::sleep(WORKER_IDLE_SLEEP);
+            }
+        }
+    })
+}
+
 fn build_input_stream(
     device: &cpal::Device,
     supported_config: &SupportedStreamConfig,
-    inner: Arc<Mutex<EngineInner>>,
+    capture_tx: HeapProducer<i16>,
+    atomics: Arc<Atomics>,
 ) -> Result<Stream> {
     let sample_format = supported_config.sample_format();
     let config: StreamConfig = supported_config.clone().into();
@@ -419,9 +1121,9 @@ fn build_input_stream(
     };
 
     match sample_format {
-        SampleFormat::I16 => build_input_stream_typed::<i16>(device, &config, inner, err_fn),
-        SampleFormat::U16 => build_input_stream_typed::<u16>(device, &config, inner, err_fn),
-        SampleFormat::F32 => build_input_stream_typed::<f32>(device, &config, inner, err_fn),
+        SampleFormat::I16 => build_input_stream_typed::<i16>(device, &config, capture_tx, atomics, err_fn),
+        SampleFormat::U16 => build_input_stream_typed::<u16>(device, &config, capture_tx, atomics, err_fn),
+        SampleFormat::F32 => build_input_stream_typed::<f32>(device, &config, capture_tx, atomics, err_fn),
         other => Err(Error::from_reason(format!("unsupported input sample format: {other:?}"))),
     }
 }
@@ -429,7 +1131,8 @@ fn build_input_stream(
 fn build_input_stream_typed<T>(
     device: &cpal::Device,
     config: &StreamConfig,
-    inner: Arc<Mutex<EngineInner>>,
+    mut capture_tx: HeapProducer<i16>,
+    atomics: Arc<Atomics>,
     err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
 ) -> Result<Stream>
 where
@@ -441,10 +1144,10 @@ where
         .build_input_stream(
             config,
             move |data: &[T], _| {
-                if let Ok(mut guard) = inner.lock() {
-                    for frame in data.chunks(channels) {
-                        if let Some(sample) = frame.first() {
-                            guard.on_captured_device_sample(i16::from_sample(*sample));
+                for frame in data.chunks(channels) {
+                    if let Some(sample) = frame.first() {
+                        if capture_tx.push(i16::from_sample(*sample)).is_err() {
+                            atomics.dropped_raw_frames.fetch_add(1, Ordering::Relaxed);
                         }
                     }
                 }
@@ -459,7 +1162,8 @@ where
 fn build_output_stream(
     device: &cpal::Device,
     supported_config: &SupportedStreamConfig,
-    inner: Arc<Mutex<EngineInner>>,
+    output_rx: HeapConsumer<i16>,
+    atomics: Arc<Atomics>,
 ) -> Result<Stream> {
     let sample_format = supported_config.sample_format();
     let config: StreamConfig = supported_config.clone().into();
@@ -469,9 +1173,9 @@ fn build_output_stream(
     };
 
     match sample_format {
-        SampleFormat::I16 => build_output_stream_typed::<i16>(device, &config, inner, err_fn),
-        SampleFormat::U16 => build_output_stream_typed::<u16>(device, &config, inner, err_fn),
-        SampleFormat::F32 => build_output_stream_typed::<f32>(device, &config, inner, err_fn),
+        SampleFormat::I16 => build_output_stream_typed::<i16>(device, &config, output_rx, atomics, err_fn),
+        SampleFormat::U16 => build_output_stream_typed::<u16>(device, &config, output_rx, atomics, err_fn),
+        SampleFormat::F32 => build_output_stream_typed::<f32>(device, &config, output_rx, atomics, err_fn),
         other => Err(Error::from_reason(format!("unsupported output sample format: {other:?}"))),
     }
 }
@@ -479,7 +1183,8 @@ fn build_output_stream(
 fn build_output_stream_typed<T>(
     device: &cpal::Device,
     config: &StreamConfig,
-    inner: Arc<Mutex<EngineInner>>,
+    mut output_rx: HeapConsumer<i16>,
+    atomics: Arc<Atomics>,
     err_fn: impl FnMut(cpal::StreamError) + Send + 'static,
 ) -> Result<Stream>
 where
@@ -490,17 +1195,17 @@ where
         .build_output_stream(
             config,
             move |data: &mut [T], _| {
-                if let Ok(mut guard) = inner.lock() {
-                    for frame in data.chunks_mut(channels) {
-                        let sample = guard.next_output_sample();
-                        let converted = T::from_sample(sample);
-                        for out in frame {
-                            *out = converted;
+                for frame in data.chunks_mut(channels) {
+                    let sample = match output_rx.pop() {
+                        Some(sample) => sample,
+                        None => {
+                            atomics.playback_underruns.fetch_add(1, Ordering::Relaxed);
+                            0
                         }
-                    }
-                } else {
-                    for out in data.iter_mut() {
-                        *out = T::from_sample(0i16);
+                    };
+                    let converted = T::from_sample(sample);
+                    for out in frame {
+                        *out = converted;
                     }
                 }
             },
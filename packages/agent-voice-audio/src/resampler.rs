@@ -0,0 +1,141 @@
+//! Windowed-sinc polyphase resampling used to convert between device sample
+//! rates and the fixed rate the APM expects.
+//!
+//! Each phase of the bank is a Kaiser-windowed sinc sampled at the fractional
+//! offset that phase represents, with the sinc cutoff set so that
+//! downsampling stays band-limited. Callers keep a rolling history of the
+//! last `RESAMPLER_TAPS` input samples and pick a phase based on how far the
+//! desired output instant sits between two input samples.
+
+use std::f64::consts::PI;
+
+pub const RESAMPLER_PHASES: usize = 128;
+pub const RESAMPLER_TAPS: usize = 24;
+
+const KAISER_BETA: f64 = 8.6;
+
+pub struct PolyphaseFilterBank {
+    phases: Vec<[f32; RESAMPLER_TAPS]>,
+}
+
+impl PolyphaseFilterBank {
+    pub fn new(in_rate: u32, out_rate: u32) -> Self {
+        let in_rate = in_rate.max(1) as f64;
+        let out_rate = out_rate.max(1) as f64;
+        // Normalized against `in_rate`, the rate the tap history is actually
+        // sampled at, not `max(in_rate, out_rate)`. Downsampling still needs
+        // the cutoff pulled in to `out_rate`'s Nyquist to stay band-limited;
+        // upsampling has nothing to alias, so this gives 0.5 (no extra
+        // attenuation) instead of needlessly low-passing the signal.
+        let cutoff = (in_rate.min(out_rate) / (2.0 * in_rate)).min(0.5);
+
+        let phases = (0..RESAMPLER_PHASES)
+            .map(|phase| build_phase(phase as f64 / RESAMPLER_PHASES as f64, cutoff))
+            .collect();
+
+        Self { phases }
+    }
+
+    /// Map a fractional position in `[0, 1)` between two input samples to the
+    /// nearest phase in the bank.
+    pub fn phase_for_frac(frac: f64) -> usize {
+        let rounded = (frac * RESAMPLER_PHASES as f64).round() as i64;
+        rounded.rem_euclid(RESAMPLER_PHASES as i64) as usize
+    }
+
+    /// Convolve the `RESAMPLER_TAPS` most recent input samples (oldest
+    /// first) against the chosen phase's coefficients.
+    pub fn convolve(&self, history: &[i16; RESAMPLER_TAPS], phase: usize) -> i16 {
+        let coeffs = &self.phases[phase.min(RESAMPLER_PHASES - 1)];
+        let mut acc = 0f32;
+        for (coeff, sample) in coeffs.iter().zip(history.iter()) {
+            acc += coeff * *sample as f32;
+        }
+        acc.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+}
+
+fn build_phase(frac: f64, cutoff: f64) -> [f32; RESAMPLER_TAPS] {
+    let half = RESAMPLER_TAPS as f64 / 2.0;
+    let mut coeffs = [0f64; RESAMPLER_TAPS];
+    let mut sum = 0f64;
+
+    for (m, coeff) in coeffs.iter_mut().enumerate() {
+        let x = (m as f64 - half) - frac;
+        let value = sinc(2.0 * cutoff * x) * 2.0 * cutoff * kaiser_window(m, RESAMPLER_TAPS, KAISER_BETA);
+        *coeff = value;
+        sum += value;
+    }
+
+    let mut out = [0f32; RESAMPLER_TAPS];
+    if sum.abs() > f64::EPSILON {
+        for (dst, src) in out.iter_mut().zip(coeffs.iter()) {
+            *dst = (*src / sum) as f32;
+        }
+    }
+    out
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+fn kaiser_window(m: usize, taps: usize, beta: f64) -> f64 {
+    let n = taps as f64 - 1.0;
+    let a = (2.0 * m as f64 / n) - 1.0;
+    bessel_i0(beta * (1.0 - a * a).max(0.0).sqrt()) / bessel_i0(beta)
+}
+
+/// Zeroth-order modified Bessel function, used by the Kaiser window.
+fn bessel_i0(x: f64) -> f64 {
+    let y = x * x / 4.0;
+    let mut term = 1.0;
+    let mut sum = 1.0;
+    for k in 1..20 {
+        term *= y / (k * k) as f64;
+        sum += term;
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// DTFT magnitude of `coeffs` (tap-spaced at `sample_rate`) at `freq_hz`.
+    fn gain_at(coeffs: &[f32; RESAMPLER_TAPS], freq_hz: f64, sample_rate: f64) -> f64 {
+        let mut re = 0f64;
+        let mut im = 0f64;
+        for (m, coeff) in coeffs.iter().enumerate() {
+            let theta = -2.0 * PI * freq_hz * m as f64 / sample_rate;
+            re += *coeff as f64 * theta.cos();
+            im += *coeff as f64 * theta.sin();
+        }
+        (re * re + im * im).sqrt()
+    }
+
+    #[test]
+    fn upsampling_passes_the_full_band_unattenuated() {
+        // 24kHz target resampled up to a 48kHz device: there's nothing to
+        // alias, so the filter must not low-pass the signal at all.
+        let bank = PolyphaseFilterBank::new(24_000, 48_000);
+        let coeffs = &bank.phases[0];
+        assert!(gain_at(coeffs, 6_000.0, 24_000.0) > 0.95);
+        assert!(gain_at(coeffs, 11_000.0, 24_000.0) > 0.95);
+    }
+
+    #[test]
+    fn downsampling_attenuates_above_the_target_nyquist() {
+        // 48kHz device capture resampled down to a 24kHz target: anything
+        // above 12kHz must be knocked down or it will alias into the
+        // passband.
+        let bank = PolyphaseFilterBank::new(48_000, 24_000);
+        let coeffs = &bank.phases[0];
+        assert!(gain_at(coeffs, 6_000.0, 48_000.0) > 0.9);
+        assert!(gain_at(coeffs, 18_000.0, 48_000.0) < 0.1);
+    }
+}
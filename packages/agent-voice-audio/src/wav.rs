@@ -0,0 +1,69 @@
+//! Minimal incremental WAV writer for debugging AEC quality and voice-note
+//! recording. A placeholder header is written up front, PCM is appended as
+//! frames arrive so nothing has to be buffered in memory, and the RIFF/data
+//! chunk sizes are patched in once the final length is known in `finalize`.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Seek, SeekFrom, Write};
+
+const CHANNELS: u16 = 1;
+const BITS_PER_SAMPLE: u16 = 16;
+
+pub struct WavWriter {
+    file: BufWriter<File>,
+    data_len: u32,
+}
+
+impl WavWriter {
+    /// Create `path` and write a placeholder header for mono 16-bit PCM at
+    /// `sample_rate`; the size fields assume an empty data chunk here and are patched up once
+    /// `finalize` knows the final data length.
+    pub fn create(path: &str, sample_rate: u32) -> io::Result<Self> {
+        let mut file = BufWriter::new(File::create(path)?);
+        write_header(&mut file, sample_rate, 0)?;
+        Ok(Self { file, data_len: 0 })
+    }
+
+    /// Append already little-endian-encoded 16-bit PCM and flush, so a crash
+    /// mid-recording only costs the still-placeholder header rather than
+    /// unwritten audio.
+    pub fn write_pcm16_le(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.file.write_all(bytes)?;
+        self.data_len = self.data_len.saturating_add(bytes.len() as u32);
+        self.file.flush()
+    }
+
+    /// Patch the RIFF and data chunk sizes now that the final length is
+    /// known, and flush.
+    pub fn finalize(mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let mut file = self.file.into_inner().map_err(|err| err.into_error())?;
+        file.seek(SeekFrom::Start(4))?;
+        file.write_all(&(36 + self.data_len).to_le_bytes())?;
+        file.seek(SeekFrom::Start(40))?;
+        file.write_all(&self.data_len.to_le_bytes())?;
+        file.flush()
+    }
+}
+
+fn write_header(file: &mut BufWriter<File>, sample_rate: u32, data_len: u32) -> io::Result<()> {
+    let byte_rate = sample_rate * CHANNELS as u32 * (BITS_PER_SAMPLE as u32 / 8);
+    let block_align = CHANNELS * (BITS_PER_SAMPLE / 8);
+
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_len).to_le_bytes())?;
+    file.write_all(b"WAVE")?;
+
+    file.write_all(b"fmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?; // PCM
+    file.write_all(&CHANNELS.to_le_bytes())?;
+    file.write_all(&sample_rate.to_le_bytes())?;
+    file.write_all(&byte_rate.to_le_bytes())?;
+    file.write_all(&block_align.to_le_bytes())?;
+    file.write_all(&BITS_PER_SAMPLE.to_le_bytes())?;
+
+    file.write_all(b"data")?;
+    file.write_all(&data_len.to_le_bytes())?;
+    Ok(())
+}